@@ -0,0 +1,251 @@
+#![allow(unused)]
+//! A lightweight, panic-free conformance checker for [`GymEnv`], in the spirit of Gymnasium's
+//! own `check_env` utility: it probes an environment and reports mismatches between this
+//! crate's assumptions (flat `Vec<f32>` shapes, finite rewards, boolean terminal flags) and
+//! what the underlying Python environment actually returns.
+use crate::gym_env::{GymEnvBuilder, Obs, Space};
+use pyo3::{IntoPy, PyObject, Python};
+use rand::Rng;
+
+/// A single non-fatal mismatch found while probing an environment. `check_env` never panics;
+/// callers inspect the returned warnings (an empty `Vec` means the environment conforms).
+#[derive(Debug, Clone)]
+pub enum CheckWarning {
+    /// `reset` or `step` returned an observation whose shape doesn't match the declared
+    /// `observation_space`.
+    ObsShapeMismatch {
+        path: String,
+        expected: Vec<usize>,
+        got: Vec<usize>,
+    },
+    /// `step` returned a reward that was `NaN` or infinite.
+    NonFiniteReward { step: usize, reward: f64 },
+    /// Constructing the environment, or calling `reset`/`step` on it, failed outright.
+    Error(String),
+}
+
+/// Constructs the named environment and checks it the way Gymnasium's `check_env` does:
+/// - `reset` returns an observation matching the declared `observation_space` shape;
+/// - `step` returns a finite reward and a `terminated`/`truncated` pair, for several random
+///   actions sampled from the declared `action_space`, without panicking or shape mismatches;
+/// - episode boundaries are handled by resetting and continuing the probe.
+pub fn check_env(name: &str) -> Vec<CheckWarning> {
+    let mut warnings = Vec::new();
+    let env = match GymEnvBuilder::new(name).build() {
+        Ok(env) => env,
+        Err(err) => {
+            warnings.push(CheckWarning::Error(err.to_string()));
+            return warnings;
+        }
+    };
+
+    let obs = match env.reset(0) {
+        Ok(obs) => obs,
+        Err(err) => {
+            warnings.push(CheckWarning::Error(err.to_string()));
+            return warnings;
+        }
+    };
+    check_obs_shape("reset", &obs, env.observation_space(), &mut warnings);
+
+    const STEPS: usize = 10;
+    for step_idx in 0..STEPS {
+        let action = sample_action(env.action_space());
+        let step = match env.step(action) {
+            Ok(step) => step,
+            Err(err) => {
+                warnings.push(CheckWarning::Error(err.to_string()));
+                break;
+            }
+        };
+        if !step.reward.is_finite() {
+            warnings.push(CheckWarning::NonFiniteReward {
+                step: step_idx,
+                reward: step.reward,
+            });
+        }
+        check_obs_shape("step", &step.obs, env.observation_space(), &mut warnings);
+        if step.is_done {
+            if let Err(err) = env.reset(step_idx as u64) {
+                warnings.push(CheckWarning::Error(err.to_string()));
+                break;
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Note: `GymEnv` already reshapes every observation, including each entry of a `Dict`
+/// observation, into its declared `observation_space` shape, so a genuine mismatch between the
+/// Python environment and its declared space surfaces earlier as a `reset`/`step` error
+/// (reported as [`CheckWarning::Error`]) rather than as [`CheckWarning::ObsShapeMismatch`] here;
+/// a `Dict` observation with a missing key fails there as well. This check walks the returned
+/// observation, nested `Dict`s included, and compares each tensor's shape with its sub-space.
+fn check_obs_shape(path: &str, obs: &Obs, space: &Space, warnings: &mut Vec<CheckWarning>) {
+    match (obs, space) {
+        (Obs::Tensor(tensor), Space::Discrete(_)) => {
+            if !tensor.dims().is_empty() {
+                warnings.push(CheckWarning::ObsShapeMismatch {
+                    path: path.to_string(),
+                    expected: vec![],
+                    got: tensor.dims().to_vec(),
+                });
+            }
+        }
+        (Obs::Tensor(tensor), Space::MultiDiscrete(nvec)) => {
+            let expected = vec![nvec.len()];
+            if tensor.dims() != expected {
+                warnings.push(CheckWarning::ObsShapeMismatch {
+                    path: path.to_string(),
+                    expected,
+                    got: tensor.dims().to_vec(),
+                });
+            }
+        }
+        (Obs::Tensor(tensor), Space::Box { shape, .. }) => {
+            if tensor.dims() != shape.as_slice() {
+                warnings.push(CheckWarning::ObsShapeMismatch {
+                    path: path.to_string(),
+                    expected: shape.clone(),
+                    got: tensor.dims().to_vec(),
+                });
+            }
+        }
+        (Obs::Dict(obs), Space::Dict(spaces)) => {
+            for (key, sub_space) in spaces {
+                match obs.get(key) {
+                    Some(sub_obs) => {
+                        check_obs_shape(&format!("{path}.{key}"), sub_obs, sub_space, warnings)
+                    }
+                    None => warnings.push(CheckWarning::Error(format!(
+                        "{path}: missing key {key} in Dict observation"
+                    ))),
+                }
+            }
+        }
+        (obs, space) => warnings.push(CheckWarning::Error(format!(
+            "{path}: observation {obs:?} does not match declared space {space:?}"
+        ))),
+    }
+}
+
+/// A randomly sampled action, carrying enough type information to be converted back into a
+/// Python object regardless of whether the action space is discrete, continuous or a `Dict`.
+enum SampledAction {
+    Discrete(i64),
+    MultiDiscrete(Vec<i64>),
+    Continuous(Vec<f32>),
+    Dict(Vec<(String, SampledAction)>),
+}
+
+impl IntoPy<PyObject> for SampledAction {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            SampledAction::Discrete(v) => v.into_py(py),
+            SampledAction::MultiDiscrete(v) => v.into_py(py),
+            SampledAction::Continuous(v) => v.into_py(py),
+            SampledAction::Dict(entries) => entries
+                .into_iter()
+                .map(|(key, action)| (key, action.into_py(py)))
+                .collect::<std::collections::HashMap<String, PyObject>>()
+                .into_py(py),
+        }
+    }
+}
+
+fn sample_action(space: &Space) -> SampledAction {
+    let mut rng = rand::thread_rng();
+    match space {
+        Space::Discrete(n) => SampledAction::Discrete(rng.gen_range(0..*n) as i64),
+        Space::MultiDiscrete(nvec) => SampledAction::MultiDiscrete(
+            nvec.iter().map(|n| rng.gen_range(0..*n) as i64).collect(),
+        ),
+        Space::Box { shape, low, high } => {
+            let len = shape.iter().product::<usize>().max(1);
+            SampledAction::Continuous(
+                (0..len)
+                    .map(|i| {
+                        let lo = low.get(i).copied().unwrap_or(-1.0);
+                        let hi = high.get(i).copied().unwrap_or(1.0);
+                        // Some Box spaces declare unbounded or inverted bounds (e.g. +/-inf, or
+                        // a low/high pair that doesn't actually bracket a range); sampling must
+                        // never panic, so fall back to 0 in that case.
+                        if lo.is_finite() && hi.is_finite() && lo < hi {
+                            rng.gen_range(lo..=hi)
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect(),
+            )
+        }
+        Space::Dict(spaces) => SampledAction::Dict(
+            spaces
+                .iter()
+                .map(|(key, sub_space)| (key.clone(), sample_action(sub_space)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_action_handles_empty_box_bounds() {
+        let space = Space::Box {
+            shape: vec![2],
+            low: vec![],
+            high: vec![],
+        };
+        match sample_action(&space) {
+            SampledAction::Continuous(v) => {
+                // Missing bounds default to [-1, 1].
+                assert_eq!(v.len(), 2);
+                assert!(v.iter().all(|x| (-1.0..=1.0).contains(x)));
+            }
+            _ => panic!("expected a Continuous action"),
+        }
+    }
+
+    #[test]
+    fn sample_action_handles_inverted_box_bounds() {
+        let space = Space::Box {
+            shape: vec![1],
+            low: vec![5.0],
+            high: vec![-5.0],
+        };
+        match sample_action(&space) {
+            SampledAction::Continuous(v) => assert_eq!(v, vec![0.0]),
+            _ => panic!("expected a Continuous action"),
+        }
+    }
+
+    #[test]
+    fn sample_action_handles_non_finite_box_bounds() {
+        let space = Space::Box {
+            shape: vec![1],
+            low: vec![f32::NEG_INFINITY],
+            high: vec![f32::INFINITY],
+        };
+        match sample_action(&space) {
+            SampledAction::Continuous(v) => assert_eq!(v, vec![0.0]),
+            _ => panic!("expected a Continuous action"),
+        }
+    }
+
+    #[test]
+    fn sample_action_samples_within_well_formed_box_bounds() {
+        let space = Space::Box {
+            shape: vec![1],
+            low: vec![-1.0],
+            high: vec![1.0],
+        };
+        match sample_action(&space) {
+            SampledAction::Continuous(v) => assert!(v[0] >= -1.0 && v[0] <= 1.0),
+            _ => panic!("expected a Continuous action"),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+#![allow(unused)]
+//! Observation and reward filters, applied between the raw Python environment and the agent.
+use candle::{Result, Tensor};
+use std::collections::VecDeque;
+
+/// A stateful transform applied to the observation tensor coming out of `reset`/`step`.
+///
+/// `new` and `Config` require `Self: Sized` so the trait stays object-safe (filters are stored
+/// as `Box<dyn GymObsFilter<Obs>>`).
+pub trait GymObsFilter<Obs> {
+    /// Configuration used to build a fresh filter, e.g. via `Default`.
+    type Config
+    where
+        Self: Sized;
+
+    /// Creates a new filter from its configuration.
+    fn new(config: Self::Config) -> Self
+    where
+        Self: Sized;
+
+    /// Applies the filter to a raw observation, returning the transformed observation.
+    fn filt(&mut self, obs: Obs) -> Obs;
+
+    /// Resets any internal state, called whenever the underlying environment is reset.
+    fn reset(&mut self) {}
+}
+
+/// A stateful transform applied to a scalar value in the environment loop, e.g. the reward
+/// returned by `step`.
+///
+/// `new` and `Config` require `Self: Sized` so the trait stays object-safe (filters are stored
+/// as `Box<dyn GymActFilter<Act>>`).
+pub trait GymActFilter<Act> {
+    /// Configuration used to build a fresh filter, e.g. via `Default`.
+    type Config
+    where
+        Self: Sized;
+
+    /// Creates a new filter from its configuration.
+    fn new(config: Self::Config) -> Self
+    where
+        Self: Sized;
+
+    /// Applies the filter to a raw value, returning the transformed value.
+    fn filt(&mut self, act: Act) -> Act;
+
+    /// Resets any internal state, called whenever the underlying environment is reset.
+    fn reset(&mut self) {}
+}
+
+/// Configuration for [`ObsNormalizer`].
+#[derive(Debug, Clone, Copy)]
+pub struct ObsNormalizerConfig {
+    /// Small constant added to the running variance to avoid dividing by zero.
+    pub epsilon: f64,
+}
+
+impl Default for ObsNormalizerConfig {
+    fn default() -> Self {
+        Self { epsilon: 1e-8 }
+    }
+}
+
+/// Normalizes observations using a running mean/variance estimate.
+pub struct ObsNormalizer {
+    config: ObsNormalizerConfig,
+    count: f64,
+    mean: Option<Tensor>,
+    mean_sq: Option<Tensor>,
+}
+
+impl ObsNormalizer {
+    fn update(&mut self, obs: &Tensor) -> Result<()> {
+        self.count += 1.0;
+        let decay = 1.0 / self.count;
+        self.mean = Some(match &self.mean {
+            None => obs.clone(),
+            Some(mean) => ((mean * (1. - decay))? + (obs * decay)?)?,
+        });
+        let obs_sq = (obs * obs)?;
+        self.mean_sq = Some(match &self.mean_sq {
+            None => obs_sq,
+            Some(mean_sq) => ((mean_sq * (1. - decay))? + (obs_sq * decay)?)?,
+        });
+        Ok(())
+    }
+}
+
+impl GymObsFilter<Tensor> for ObsNormalizer {
+    type Config = ObsNormalizerConfig;
+
+    fn new(config: Self::Config) -> Self {
+        Self {
+            config,
+            count: 0.0,
+            mean: None,
+            mean_sq: None,
+        }
+    }
+
+    fn filt(&mut self, obs: Tensor) -> Tensor {
+        let mut normalize = || -> Result<Tensor> {
+            self.update(&obs)?;
+            let mean = self.mean.clone().unwrap();
+            let mean_sq = self.mean_sq.clone().unwrap();
+            let var = (mean_sq - (&mean * &mean)?)?;
+            let std = (var + self.config.epsilon)?.sqrt()?;
+            (&obs - mean)? / std
+        };
+        normalize().unwrap_or(obs)
+    }
+
+    fn reset(&mut self) {
+        self.count = 0.0;
+        self.mean = None;
+        self.mean_sq = None;
+    }
+}
+
+/// Configuration for [`FrameStack`].
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStackConfig {
+    /// Number of most recent observations stacked together.
+    pub n: usize,
+}
+
+/// Stacks the `n` most recent observations along a new leading dimension. On the first
+/// observation of an episode, that observation is repeated `n` times to fill the window.
+pub struct FrameStack {
+    config: FrameStackConfig,
+    frames: VecDeque<Tensor>,
+}
+
+impl FrameStack {
+    fn capacity(&self) -> usize {
+        self.config.n.max(1)
+    }
+}
+
+impl GymObsFilter<Tensor> for FrameStack {
+    type Config = FrameStackConfig;
+
+    fn new(config: Self::Config) -> Self {
+        Self {
+            config,
+            frames: VecDeque::new(),
+        }
+    }
+
+    fn filt(&mut self, obs: Tensor) -> Tensor {
+        if self.frames.is_empty() {
+            for _ in 0..self.capacity() {
+                self.frames.push_back(obs.clone());
+            }
+        } else {
+            self.frames.push_back(obs.clone());
+            if self.frames.len() > self.capacity() {
+                self.frames.pop_front();
+            }
+        }
+        let frames: Vec<_> = self.frames.iter().collect();
+        Tensor::stack(&frames, 0).unwrap_or(obs)
+    }
+
+    fn reset(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Configuration for [`RewardClip`].
+#[derive(Debug, Clone, Copy)]
+pub struct RewardClipConfig {
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Clips the reward returned by `step` to `[min, max]`.
+pub struct RewardClip {
+    config: RewardClipConfig,
+}
+
+impl GymActFilter<f64> for RewardClip {
+    type Config = RewardClipConfig;
+
+    fn new(config: Self::Config) -> Self {
+        Self { config }
+    }
+
+    fn filt(&mut self, act: f64) -> f64 {
+        act.clamp(self.config.min, self.config.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obs_normalizer_converges_to_zero_mean_unit_variance() {
+        let mut filter = ObsNormalizer::new(ObsNormalizerConfig::default());
+        let samples = [-1f32, 1., -1., 1., -1., 1., -1., 1.];
+        let mut last = None;
+        for &x in samples.iter() {
+            let obs = Tensor::new(&[x], &candle::Device::Cpu).unwrap();
+            last = Some(filter.filt(obs));
+        }
+        let out = last.unwrap().to_vec1::<f32>().unwrap();
+        // The running mean is ~0 and the running variance is ~1, so the normalized sample
+        // should stay close to its raw value.
+        assert!((out[0].abs() - 1.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn obs_normalizer_reset_clears_running_stats() {
+        let mut filter = ObsNormalizer::new(ObsNormalizerConfig::default());
+        let obs = Tensor::new(&[10f32], &candle::Device::Cpu).unwrap();
+        filter.filt(obs);
+        filter.reset();
+        assert_eq!(filter.count, 0.0);
+        assert!(filter.mean.is_none());
+    }
+
+    #[test]
+    fn frame_stack_repeats_first_obs_to_fill_window() {
+        let mut filter = FrameStack::new(FrameStackConfig { n: 3 });
+        let obs = Tensor::new(&[1f32, 2.], &candle::Device::Cpu).unwrap();
+        let stacked = filter.filt(obs).dims().to_vec();
+        assert_eq!(stacked, vec![3, 2]);
+    }
+
+    #[test]
+    fn frame_stack_drops_oldest_frame_once_full() {
+        let mut filter = FrameStack::new(FrameStackConfig { n: 2 });
+        filter.filt(Tensor::new(&[1f32], &candle::Device::Cpu).unwrap());
+        let stacked = filter.filt(Tensor::new(&[2f32], &candle::Device::Cpu).unwrap());
+        let values = stacked.flatten_all().unwrap().to_vec1::<f32>().unwrap();
+        assert_eq!(values, vec![1., 2.]);
+    }
+
+    #[test]
+    fn reward_clip_clamps_to_bounds() {
+        let mut filter = RewardClip::new(RewardClipConfig { min: -1.0, max: 1.0 });
+        assert_eq!(filter.filt(5.0), 1.0);
+        assert_eq!(filter.filt(-5.0), -1.0);
+        assert_eq!(filter.filt(0.5), 0.5);
+    }
+}
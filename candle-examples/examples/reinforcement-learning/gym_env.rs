@@ -1,108 +1,387 @@
 #![allow(unused)]
 //! Wrappers around the Python API of Gymnasium (the new version of OpenAI gym)
+use crate::filter::{GymActFilter, GymObsFilter};
 use candle::{Device, Result, Tensor};
 use pyo3::prelude::*;
 use pyo3::types::PyDict;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// The shape (and, for `Box` spaces, the bounds) of an observation or action space, populated
+/// by inspecting the corresponding Python `gymnasium.spaces` object. This lets us go beyond
+/// flat vector observations/actions to images, multi-discrete and dictionary spaces.
+#[derive(Debug, Clone)]
+pub enum Space {
+    /// A single value in `0..n`, e.g. a button press.
+    Discrete(usize),
+    /// A vector of independent discrete values, each with its own cardinality.
+    MultiDiscrete(Vec<usize>),
+    /// A dense array of continuous (or discrete-but-dense) values with the given shape, e.g.
+    /// a joint-angle vector or an RGB image.
+    Box {
+        shape: Vec<usize>,
+        low: Vec<f32>,
+        high: Vec<f32>,
+    },
+    /// A named collection of sub-spaces, e.g. `{"image": Box(..), "position": Box(..)}`.
+    Dict(HashMap<String, Space>),
+}
+
+impl Space {
+    fn from_pyobject(space: &PyAny) -> PyResult<Space> {
+        let class_name = space.get_type().name()?.to_string();
+        match class_name.as_str() {
+            "Discrete" => {
+                let n: usize = space.getattr("n")?.extract()?;
+                Ok(Space::Discrete(n))
+            }
+            "MultiDiscrete" => {
+                let nvec: Vec<usize> = space.getattr("nvec")?.call_method0("tolist")?.extract()?;
+                Ok(Space::MultiDiscrete(nvec))
+            }
+            "Dict" => {
+                let spaces: &PyDict = space.getattr("spaces")?.extract()?;
+                let mut map = HashMap::new();
+                for (key, value) in spaces.iter() {
+                    let key: String = key.extract()?;
+                    map.insert(key, Space::from_pyobject(value)?);
+                }
+                Ok(Space::Dict(map))
+            }
+            _ => {
+                let shape: Vec<usize> = space.getattr("shape")?.extract()?;
+                let low: Vec<f32> = space.getattr("low")?.call_method0("flatten")?.extract()?;
+                let high: Vec<f32> = space.getattr("high")?.call_method0("flatten")?.extract()?;
+                Ok(Space::Box { shape, low, high })
+            }
+        }
+    }
+}
+
+/// An observation, either a single tensor (for `Discrete`, `MultiDiscrete` and `Box` spaces) or
+/// a named collection of observations (for `Dict` spaces, which may themselves nest `Dict`
+/// sub-spaces).
+#[derive(Debug, Clone)]
+pub enum Obs {
+    Tensor(Tensor),
+    Dict(HashMap<String, Obs>),
+}
+
+impl Obs {
+    /// Returns the single tensor, panicking if this observation came from a `Dict` space.
+    pub fn tensor(self) -> Tensor {
+        match self {
+            Obs::Tensor(t) => t,
+            Obs::Dict(_) => panic!("expected a flat observation, got a Dict observation"),
+        }
+    }
+}
+
+/// An intermediate, GIL-free representation of a raw observation, extracted from Python inside
+/// `Python::with_gil` and turned into tensors afterwards.
+enum RawObs {
+    Flat(Vec<f32>, Vec<usize>),
+    Dict(HashMap<String, RawObs>),
+}
+
+fn extract_raw_obs(obs: &PyAny, space: &Space) -> PyResult<RawObs> {
+    match space {
+        Space::Dict(spaces) => {
+            let dict: &PyDict = obs.extract()?;
+            let mut map = HashMap::new();
+            for (key, sub_space) in spaces.iter() {
+                let value = dict
+                    .get_item(key)?
+                    .ok_or_else(|| PyErr::new::<pyo3::exceptions::PyKeyError, _>(key.clone()))?;
+                map.insert(key.clone(), extract_raw_obs(value, sub_space)?);
+            }
+            Ok(RawObs::Dict(map))
+        }
+        Space::Discrete(_) => {
+            let v: f32 = obs.extract()?;
+            Ok(RawObs::Flat(vec![v], vec![]))
+        }
+        Space::MultiDiscrete(nvec) => {
+            let v: Vec<f32> = obs.call_method0("flatten")?.extract()?;
+            Ok(RawObs::Flat(v, vec![nvec.len()]))
+        }
+        Space::Box { shape, .. } => {
+            let v: Vec<f32> = obs.call_method0("flatten")?.extract()?;
+            Ok(RawObs::Flat(v, shape.clone()))
+        }
+    }
+}
+
+fn raw_obs_to_obs(raw: RawObs) -> Result<Obs> {
+    match raw {
+        RawObs::Flat(data, shape) => Ok(Obs::Tensor(Tensor::new(data, &Device::Cpu)?.reshape(shape)?)),
+        RawObs::Dict(map) => {
+            let mut obs = HashMap::new();
+            for (key, value) in map {
+                obs.insert(key, raw_obs_to_obs(value)?);
+            }
+            Ok(Obs::Dict(obs))
+        }
+    }
+}
 
 /// The return value for a step.
 #[derive(Debug)]
 pub struct Step<A> {
-    pub obs: Tensor,
+    pub obs: Obs,
     pub action: A,
     pub reward: f64,
+    /// The episode ended in a true terminal state (e.g. the agent died or reached the goal).
+    pub terminated: bool,
+    /// The episode was cut short by a time limit or other external condition, not by reaching
+    /// a terminal state. Bootstrapping should still use the value of `obs` in this case.
+    pub truncated: bool,
+    /// `terminated || truncated`, kept for backward compatibility with callers that only care
+    /// whether the episode is over, regardless of the reason.
     pub is_done: bool,
+    /// The `[H, W, 3]` uint8 RGB render of the environment after this step, present when the
+    /// owning [`GymEnv`] was built with [`GymEnvBuilder::return_image`].
+    pub rgb: Option<Tensor>,
 }
 
 impl<A: Copy> Step<A> {
-    /// Returns a copy of this step changing the observation tensor.
-    pub fn copy_with_obs(&self, obs: &Tensor) -> Step<A> {
+    /// Returns a copy of this step changing the observation.
+    pub fn copy_with_obs(&self, obs: &Obs) -> Step<A> {
         Step {
             obs: obs.clone(),
             action: self.action,
             reward: self.reward,
+            terminated: self.terminated,
+            truncated: self.truncated,
             is_done: self.is_done,
+            rgb: self.rgb.clone(),
         }
     }
 }
 
 /// An OpenAI Gym session.
+///
+/// `reset`/`step` apply an optional [`GymObsFilter`] to the observation (the same filter
+/// instance, and so the same filter state, to each tensor of a `Dict` observation in turn) and
+/// an optional [`GymActFilter`] to the reward; both are reset whenever the environment is reset.
 pub struct GymEnv {
     env: PyObject,
-    action_space: usize,
-    observation_space: Vec<usize>,
+    action_space: Space,
+    observation_space: Space,
+    obs_filter: RefCell<Option<Box<dyn GymObsFilter<Tensor>>>>,
+    reward_filter: RefCell<Option<Box<dyn GymActFilter<f64>>>>,
+    frameskip: usize,
+    return_image: bool,
 }
 
 fn w(res: PyErr) -> candle::Error {
     candle::Error::wrap(res)
 }
 
-impl GymEnv {
-    /// Creates a new session of the specified OpenAI Gym environment.
-    pub fn new(name: &str) -> Result<GymEnv> {
+fn w_rev(err: candle::Error) -> PyErr {
+    pyo3::exceptions::PyRuntimeError::new_err(err.to_string())
+}
+
+/// Builds a [`GymEnv`], following the plangym convention of configuring environments through a
+/// fluent builder rather than a long constructor argument list.
+pub struct GymEnvBuilder {
+    name: String,
+    obs_filter: Option<Box<dyn GymObsFilter<Tensor>>>,
+    reward_filter: Option<Box<dyn GymActFilter<f64>>>,
+    frameskip: usize,
+    return_image: bool,
+}
+
+impl GymEnvBuilder {
+    /// Creates a builder for the specified OpenAI Gym environment.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            obs_filter: None,
+            reward_filter: None,
+            frameskip: 1,
+            return_image: false,
+        }
+    }
+
+    /// Applies an observation filter to every tensor returned by `reset`/`step`.
+    pub fn obs_filter(mut self, obs_filter: Box<dyn GymObsFilter<Tensor>>) -> Self {
+        self.obs_filter = Some(obs_filter);
+        self
+    }
+
+    /// Applies a reward filter to the reward returned by `step`.
+    pub fn reward_filter(mut self, reward_filter: Box<dyn GymActFilter<f64>>) -> Self {
+        self.reward_filter = Some(reward_filter);
+        self
+    }
+
+    /// Repeats each action `n` times within a single `step` call, summing the rewards and
+    /// returning the final observation. The episode is marked done if any of the repeated
+    /// steps ends it. Defaults to `1`, i.e. no frameskip.
+    pub fn frameskip(mut self, n: usize) -> Self {
+        self.frameskip = n.max(1);
+        self
+    }
+
+    /// When enabled, every [`Step`] carries an `[H, W, 3]` uint8 RGB render of the environment
+    /// in `rgb`, obtained via `env.render()` with `render_mode="rgb_array"`.
+    pub fn return_image(mut self, return_image: bool) -> Self {
+        self.return_image = return_image;
+        self
+    }
+
+    /// Builds the environment, creating the underlying Python session.
+    pub fn build(self) -> Result<GymEnv> {
         Python::with_gil(|py| {
             let gym = py.import("gymnasium")?;
             let make = gym.getattr("make")?;
-            let env = make.call1((name,))?;
-            let action_space = env.getattr("action_space")?;
-            let action_space = if let Ok(val) = action_space.getattr("n") {
-                val.extract()?
+            let env = if self.return_image {
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("render_mode", "rgb_array")?;
+                make.call((self.name.as_str(),), Some(kwargs))?
             } else {
-                let action_space: Vec<usize> = action_space.getattr("shape")?.extract()?;
-                action_space[0]
+                make.call1((self.name.as_str(),))?
             };
-            let observation_space = env.getattr("observation_space")?;
-            let observation_space = observation_space.getattr("shape")?.extract()?;
+            let action_space = Space::from_pyobject(env.getattr("action_space")?)?;
+            let observation_space = Space::from_pyobject(env.getattr("observation_space")?)?;
             Ok(GymEnv {
                 env: env.into(),
                 action_space,
                 observation_space,
+                obs_filter: RefCell::new(self.obs_filter),
+                reward_filter: RefCell::new(self.reward_filter),
+                frameskip: self.frameskip,
+                return_image: self.return_image,
             })
         })
         .map_err(w)
     }
+}
+
+impl GymEnv {
+    /// Creates a new session of the specified OpenAI Gym environment, optionally applying an
+    /// observation filter and a reward filter. Equivalent to
+    /// `GymEnvBuilder::new(name).obs_filter(...).reward_filter(...).build()` with no frameskip
+    /// and no image capture; use [`GymEnvBuilder`] directly to configure those.
+    pub fn new(
+        name: &str,
+        obs_filter: Option<Box<dyn GymObsFilter<Tensor>>>,
+        reward_filter: Option<Box<dyn GymActFilter<f64>>>,
+    ) -> Result<GymEnv> {
+        let mut builder = GymEnvBuilder::new(name);
+        if let Some(obs_filter) = obs_filter {
+            builder = builder.obs_filter(obs_filter);
+        }
+        if let Some(reward_filter) = reward_filter {
+            builder = builder.reward_filter(reward_filter);
+        }
+        builder.build()
+    }
+
+    fn render_rgb(&self, py: Python) -> PyResult<Tensor> {
+        let frame = self.env.call_method0(py, "render")?;
+        let shape: Vec<usize> = frame.as_ref(py).getattr("shape")?.extract()?;
+        let data: Vec<u8> = frame.as_ref(py).call_method0("flatten")?.extract()?;
+        Tensor::from_vec(data, shape, &Device::Cpu).map_err(w_rev)
+    }
+
+    fn filter(&self, obs: Obs) -> Obs {
+        let mut filter = self.obs_filter.borrow_mut();
+        let Some(filter) = filter.as_mut() else {
+            return obs;
+        };
+        Self::apply_filter(obs, filter.as_mut())
+    }
+
+    fn apply_filter(obs: Obs, filter: &mut dyn GymObsFilter<Tensor>) -> Obs {
+        match obs {
+            Obs::Tensor(t) => Obs::Tensor(filter.filt(t)),
+            Obs::Dict(map) => Obs::Dict(
+                map.into_iter()
+                    .map(|(key, o)| (key, Self::apply_filter(o, filter)))
+                    .collect(),
+            ),
+        }
+    }
 
-    /// Resets the environment, returning the observation tensor.
-    pub fn reset(&self, seed: u64) -> Result<Tensor> {
-        let obs: Vec<f32> = Python::with_gil(|py| {
+    fn filter_reward(&self, reward: f64) -> f64 {
+        match self.reward_filter.borrow_mut().as_mut() {
+            Some(filter) => filter.filt(reward),
+            None => reward,
+        }
+    }
+
+    /// Resets the environment, returning the observation.
+    pub fn reset(&self, seed: u64) -> Result<Obs> {
+        let raw = Python::with_gil(|py| {
             let kwargs = PyDict::new(py);
             kwargs.set_item("seed", seed)?;
             let obs = self.env.call_method(py, "reset", (), Some(kwargs))?;
-            obs.as_ref(py).get_item(0)?.extract()
+            extract_raw_obs(obs.as_ref(py).get_item(0)?, &self.observation_space)
         })
         .map_err(w)?;
-        Tensor::new(obs, &Device::Cpu)
+        if let Some(filter) = self.obs_filter.borrow_mut().as_mut() {
+            filter.reset();
+        }
+        if let Some(filter) = self.reward_filter.borrow_mut().as_mut() {
+            filter.reset();
+        }
+        Ok(self.filter(raw_obs_to_obs(raw)?))
     }
 
-    /// Applies an environment step using the specified action.
+    /// Applies an environment step using the specified action. If the environment was built
+    /// with a frameskip greater than one, the action is repeated that many times within a
+    /// single `Python::with_gil` block, summing rewards and stopping early if an intermediate
+    /// step ends the episode.
     pub fn step<A: pyo3::IntoPy<pyo3::Py<pyo3::PyAny>> + Clone>(
         &self,
         action: A,
     ) -> Result<Step<A>> {
-        let (obs, reward, is_done) = Python::with_gil(|py| {
-            let step = self.env.call_method(py, "step", (action.clone(),), None)?;
-            let step = step.as_ref(py);
-            let obs: Vec<f32> = step.get_item(0)?.extract()?;
-            let reward: f64 = step.get_item(1)?.extract()?;
-            let is_done: bool = step.get_item(2)?.extract()?;
-            Ok((obs, reward, is_done))
+        let (raw, reward, terminated, truncated, rgb) = Python::with_gil(|py| {
+            let mut reward = 0f64;
+            let mut terminated = false;
+            let mut truncated = false;
+            let mut raw = None;
+            for _ in 0..self.frameskip {
+                let step = self.env.call_method(py, "step", (action.clone(),), None)?;
+                let step = step.as_ref(py);
+                raw = Some(extract_raw_obs(step.get_item(0)?, &self.observation_space)?);
+                reward += step.get_item(1)?.extract::<f64>()?;
+                terminated = step.get_item(2)?.extract()?;
+                truncated = step.get_item(3)?.extract()?;
+                if terminated || truncated {
+                    break;
+                }
+            }
+            let rgb = if self.return_image {
+                Some(self.render_rgb(py)?)
+            } else {
+                None
+            };
+            Ok((raw.unwrap(), reward, terminated, truncated, rgb))
         })
         .map_err(w)?;
-        let obs = Tensor::new(obs, &Device::Cpu)?;
+        let obs = self.filter(raw_obs_to_obs(raw)?);
+        let reward = self.filter_reward(reward);
         Ok(Step {
             obs,
             reward,
-            is_done,
+            terminated,
+            truncated,
+            is_done: terminated || truncated,
+            rgb,
             action,
         })
     }
 
-    /// Returns the number of allowed actions for this environment.
-    pub fn action_space(&self) -> usize {
-        self.action_space
+    /// Returns the action space of this environment.
+    pub fn action_space(&self) -> &Space {
+        &self.action_space
     }
 
-    /// Returns the shape of the observation tensors.
-    pub fn observation_space(&self) -> &[usize] {
+    /// Returns the observation space of this environment.
+    pub fn observation_space(&self) -> &Space {
         &self.observation_space
     }
 }
@@ -0,0 +1,119 @@
+#![allow(unused)]
+//! Wrappers around the Python API of Gymnasium (the new version of OpenAI gym), using a
+//! vectorized (multi-process) environment so that several episodes can be rolled out in
+//! parallel, as used by on-policy algorithms such as A2C or PPO.
+use candle::{Device, Result, Tensor};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+/// A vectorized OpenAI Gym session, running `n_envs` copies of the same environment in
+/// parallel sub-processes via `gymnasium.vector.AsyncVectorEnv`.
+///
+/// Unlike [`GymEnv`](crate::gym_env::GymEnv), this extracts observations as `Vec<Vec<f32>>`
+/// directly, rather than going through `gym_env`'s `Space`/`Obs` types, so it only supports
+/// one-dimensional observations (a flat `Box` or a `MultiDiscrete` space); a `Discrete`,
+/// multi-dimensional `Box` or `Dict` observation space will fail to extract. The action space
+/// is read as the `n` of a `Discrete` space or the first dimension of its shape.
+pub struct VecGymEnv {
+    env: PyObject,
+    action_space: usize,
+    observation_space: Vec<usize>,
+    n_envs: usize,
+}
+
+fn w(res: PyErr) -> candle::Error {
+    candle::Error::wrap(res)
+}
+
+impl VecGymEnv {
+    /// Creates a new session running `n_envs` copies of the specified OpenAI Gym environment.
+    pub fn new(name: &str, n_envs: usize) -> Result<VecGymEnv> {
+        Python::with_gil(|py| {
+            let gym = py.import("gymnasium")?;
+            let vector = gym.getattr("vector")?;
+            let make_vec = vector.getattr("make")?;
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("num_envs", n_envs)?;
+            let env = make_vec.call((name,), Some(kwargs))?;
+            let action_space = env.getattr("single_action_space")?;
+            let action_space = if let Ok(val) = action_space.getattr("n") {
+                val.extract()?
+            } else {
+                let action_space: Vec<usize> = action_space.getattr("shape")?.extract()?;
+                action_space[0]
+            };
+            let observation_space = env.getattr("single_observation_space")?;
+            let observation_space = observation_space.getattr("shape")?.extract()?;
+            Ok(VecGymEnv {
+                env: env.into(),
+                action_space,
+                observation_space,
+                n_envs,
+            })
+        })
+        .map_err(w)
+    }
+
+    /// Resets all the sub-environments, returning the batched observation tensor of shape
+    /// `[n_envs, ...obs_shape]`.
+    pub fn reset(&self, seed: u64) -> Result<Tensor> {
+        let obs: Vec<Vec<f32>> = Python::with_gil(|py| {
+            let kwargs = PyDict::new(py);
+            kwargs.set_item("seed", seed)?;
+            let obs = self.env.call_method(py, "reset", (), Some(kwargs))?;
+            obs.as_ref(py).get_item(0)?.extract()
+        })
+        .map_err(w)?;
+        let obs = obs
+            .iter()
+            .map(|o| Tensor::new(o.as_slice(), &Device::Cpu))
+            .collect::<Result<Vec<_>>>()?;
+        Tensor::stack(&obs, 0)
+    }
+
+    /// Returns the number of parallel sub-environments.
+    pub fn n_envs(&self) -> usize {
+        self.n_envs
+    }
+
+    /// Applies one action per sub-environment, returning the batched observation tensor along
+    /// with per-env reward and done vectors. Sub-environments that reach a terminal state are
+    /// auto-reset by the underlying vector environment, as is standard Gymnasium behaviour.
+    pub fn step<A: pyo3::IntoPy<pyo3::Py<pyo3::PyAny>> + Clone>(
+        &self,
+        actions: &[A],
+    ) -> Result<(Tensor, Vec<f64>, Vec<bool>)> {
+        let (obs, reward, is_done) = Python::with_gil(|py| {
+            let actions = actions.to_vec();
+            let step = self.env.call_method(py, "step", (actions,), None)?;
+            let step = step.as_ref(py);
+            let obs: Vec<Vec<f32>> = step.get_item(0)?.extract()?;
+            let reward: Vec<f64> = step.get_item(1)?.extract()?;
+            let terminated: Vec<bool> = step.get_item(2)?.extract()?;
+            let truncated: Vec<bool> = step.get_item(3)?.extract()?;
+            let is_done: Vec<bool> = terminated
+                .into_iter()
+                .zip(truncated)
+                .map(|(t, tr)| t || tr)
+                .collect();
+            Ok((obs, reward, is_done))
+        })
+        .map_err(w)?;
+        let obs = obs
+            .iter()
+            .map(|o| Tensor::new(o.as_slice(), &Device::Cpu))
+            .collect::<Result<Vec<_>>>()?;
+        let obs = Tensor::stack(&obs, 0)?;
+        Ok((obs, reward, is_done))
+    }
+
+    /// Returns the number of allowed actions for this environment.
+    pub fn action_space(&self) -> usize {
+        self.action_space
+    }
+
+    /// Returns the shape of the observation tensors.
+    pub fn observation_space(&self) -> &[usize] {
+        &self.observation_space
+    }
+}